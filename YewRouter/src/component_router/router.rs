@@ -12,23 +12,77 @@ use crate::YewRouterState;
 use log::{warn, trace};
 use yew_router_path_matcher::{PathMatcher};
 use yew::html::ChildrenWithProps;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use futures::future::LocalBoxFuture;
+use wasm_bindgen_futures::spawn_local;
+use yew::Callback;
 
-/// A nested component used inside of [Router](struct.Router.html) that can determine if a
-/// sub-component can be rendered.
-pub struct Route<T: for<'de> YewRouterState<'de>> {
-    props: RouteProps<T>
+/// Identifies a loaded or in-flight lazy route: its position among siblings plus its matched
+/// parameters, so a stale load from a previous match can't leak into a new one.
+type LoadKey = (usize, Vec<(String, String)>);
+
+/// A query-string key, with an optional value validator, that must be satisfied for a [Route]
+/// to match.
+#[derive(Clone)]
+pub struct RequiredQuery {
+    key: String,
+    deserialize: fn(&str) -> bool,
+}
+
+impl RequiredQuery {
+    /// Requires `key` to be present, regardless of its value.
+    pub fn present(key: impl Into<String>) -> Self {
+        RequiredQuery {
+            key: key.into(),
+            deserialize: |_| true,
+        }
+    }
+
+    /// Requires `key` to be present and `deserialize` to successfully parse its value.
+    pub fn deserializing(key: impl Into<String>, deserialize: fn(&str) -> bool) -> Self {
+        RequiredQuery {
+            key: key.into(),
+            deserialize,
+        }
+    }
+}
+
+impl PartialEq for RequiredQuery {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.deserialize as usize == other.deserialize as usize
+    }
+}
+
+/// A nested component used inside of [Router](struct.Router.html) (or [Outlet](struct.Outlet.html))
+/// that can determine if a sub-component can be rendered.
+///
+/// `COMP` is the component whose `view()` the matched route is rendered into - `Router<T>` for a
+/// top-level route, or `Outlet<T>` for a route nested under a parent layout.
+pub struct Route<T: for<'de> YewRouterState<'de>, COMP: Component = Router<T>> {
+    props: RouteProps<T, COMP>
 }
 
 /// Properties for Route.
 #[derive(Properties)]
-pub struct RouteProps<T: for<'de> YewRouterState<'de>> {
+pub struct RouteProps<T: for<'de> YewRouterState<'de>, COMP: Component = Router<T>> {
     #[props(required)]
-    pub path: PathMatcher<Router<T>>,
+    pub path: PathMatcher<COMP>,
+    /// Query-string keys (and, optionally, a value deserializer) that must be satisfied by the
+    /// current [RouteInfo] for this route to match. See [RequiredQuery].
+    #[props(required = false)]
+    pub required_query: Vec<RequiredQuery>,
+    /// Lazily loads this route's component from its matched params instead of rendering
+    /// synchronously, caching the result on the parent [Router]. Only a [Router] uses it; an
+    /// [Outlet] ignores `loader` and always renders through `path.render_fn`.
+    #[props(required = false)]
+    pub loader: Option<fn(HashMap<String, String>) -> LocalBoxFuture<'static, Html<COMP>>>,
 }
 
-impl <T: for<'de> YewRouterState<'de>> Component for Route<T> {
+impl <T: for<'de> YewRouterState<'de>, COMP: Component> Component for Route<T, COMP> {
     type Message = ();
-    type Properties = RouteProps<T>;
+    type Properties = RouteProps<T, COMP>;
 
     fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
         Route {
@@ -54,18 +108,28 @@ pub struct Router<T: for<'de> YewRouterState<'de>> {
     route: RouteInfo<T>,
     props: Props<T>,
     router_agent: Box<dyn Bridge<RouteAgent<T>>>,
+    link: ComponentLink<Self>,
+    /// Lazily-loaded route components, keyed by [LoadKey].
+    loaded: RefCell<HashMap<LoadKey, Html<Self>>>,
+    /// [LoadKey]s whose loader future is still in flight.
+    loading: RefCell<HashSet<LoadKey>>,
 }
 
 /// Message for Router.
 pub enum Msg<T> {
     UpdateRoute(RouteInfo<T>),
+    RouteComponentReady(LoadKey, Html<Router<T>>),
 }
 
 /// Properties for Router.
 #[derive(Properties)]
 pub struct Props<T: for<'de> YewRouterState<'de>> {
     #[props(required)]
-    children: ChildrenWithProps<Route<T>, Router<T>>
+    children: ChildrenWithProps<Route<T>, Router<T>>,
+    /// Rendered when no sibling [Route](struct.Route.html) matches the current
+    /// [RouteInfo](../route_info/struct.RouteInfo.html), instead of silently rendering nothing.
+    #[props(required = false)]
+    pub fallback: Option<Html<Router<T>>>,
 }
 
 impl <T> Component for Router<T>
@@ -83,6 +147,9 @@ impl <T> Component for Router<T>
             route: Default::default(), // This must be updated by immediately requesting a route update from the service bridge.
             props,
             router_agent,
+            link,
+            loaded: RefCell::new(HashMap::new()),
+            loading: RefCell::new(HashSet::new()),
         }
     }
 
@@ -96,8 +163,22 @@ impl <T> Component for Router<T>
             Msg::UpdateRoute(route) => {
                 let did_change = self.route != route;
                 self.route = route;
+                if did_change {
+                    let valid = self.matching_load_keys();
+                    self.loaded.borrow_mut().retain(|key, _| valid.contains(key));
+                    self.loading.borrow_mut().retain(|key| valid.contains(key));
+                }
                 did_change
             }
+            Msg::RouteComponentReady(key, html) => {
+                self.loading.borrow_mut().remove(&key);
+                if self.matching_load_keys().contains(&key) {
+                    self.loaded.borrow_mut().insert(key, html);
+                    true
+                } else {
+                    false
+                }
+            }
         }
     }
 
@@ -107,26 +188,80 @@ impl <T> Component for Router<T>
     }
 }
 
+impl <T: for<'de> YewRouterState<'de>> Router<T> {
+    /// The [LoadKey]s that still match a child [Route] under the current route, used to evict
+    /// `loaded`/`loading` entries left over from a previous route.
+    fn matching_load_keys(&self) -> HashSet<LoadKey> {
+        self.props.children.iter()
+            .enumerate()
+            .filter_map(|(idx, route_possibility)| {
+                if !required_query_satisfied(&route_possibility.props.required_query, &self.route.query) {
+                    return None;
+                }
+                route_possibility.props.path
+                    .match_path(&self.route)
+                    .ok()
+                    .map(|(_, mut hm)| {
+                        merge_query(&mut hm, &self.route.query);
+                        (idx, sorted_params(hm))
+                    })
+            })
+            .collect()
+    }
+}
+
 impl <T: for<'de> YewRouterState<'de>> Renderable<Router<T>> for Router<T>
 {
     fn view(&self) -> VNode<Self> {
 
         trace!("Routing one of {} routes for  {:?}", self.props.children.iter().count(), &self.route);
         self.props.children.iter()
-            .filter_map(|route_possibility| -> Option<Html<Self>> {
+            .enumerate()
+            .filter_map(|(idx, route_possibility)| -> Option<Html<Self>> {
+                if !required_query_satisfied(&route_possibility.props.required_query, &self.route.query) {
+                    return None;
+                }
+
                 route_possibility.props.path
                     .match_path(&self.route)
-                    .map(|(_rest, hm)| {
-                        (route_possibility.props.path.render_fn)(&hm)
-                    })
                     .ok()
-                    .flatten_stable()
+                    .and_then(|(rest, mut hm)| {
+                        merge_query(&mut hm, &self.route.query);
+
+                        if let Some(loader) = route_possibility.props.loader {
+                            let key = (idx, sorted_params(hm.clone()));
+                            if let Some(html) = self.loaded.borrow().get(&key) {
+                                return Some(html.clone());
+                            }
+                            if self.loading.borrow_mut().insert(key.clone()) {
+                                let mut link = self.link.clone();
+                                let owned: HashMap<String, String> = hm.into_iter()
+                                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                                    .collect();
+                                let future = loader(owned);
+                                spawn_local(async move {
+                                    let html = future.await;
+                                    link.send_self(Msg::RouteComponentReady(key, html));
+                                });
+                            }
+                            // The route did match: render nothing until its component is ready,
+                            // rather than falling through to a later sibling or the fallback.
+                            return Some(html! { <></> });
+                        }
+
+                        // `rest` is handed to the matched component as the `route` of a nested
+                        // `RouteInfo` (see `RouteInfo::with_remaining`), so its layout can embed
+                        // an `Outlet` without re-matching the full path itself.
+                        let nested_route = self.route.with_remaining(rest);
+                        (route_possibility.props.path.render_fn)(&hm, &nested_route)
+                    })
             })
             .next() // Take the first path that succeeds.
             .map(|x| -> Html<Self> {
                 trace!("Route matched.");
                 x
             })
+            .or_else(|| self.props.fallback.clone())
             .unwrap_or_else(|| {
                 warn!("Routing failed. No default case was provided.");
                 html! { <></>}
@@ -135,6 +270,154 @@ impl <T: for<'de> YewRouterState<'de>> Renderable<Router<T>> for Router<T>
 }
 
 
+/// Marks where a nested [Route](struct.Route.html)'s component should be rendered inside a
+/// parent route's layout (e.g. a shared nav wrapping page content).
+///
+/// A matched [Route] is handed a [RouteInfo] whose `route` is the unconsumed tail of the
+/// path (see [Router::view](struct.Router.html)). A layout component embeds `Outlet` with that
+/// `RouteInfo` and its own child `Route`s, which are matched only against the tail rather than
+/// the full path.
+pub struct Outlet<T: for<'de> YewRouterState<'de>> {
+    props: OutletProps<T>,
+}
+
+/// Properties for Outlet.
+#[derive(Properties)]
+pub struct OutletProps<T: for<'de> YewRouterState<'de>> {
+    /// The nested route, as produced by the enclosing [Router] or [Outlet] for the route that
+    /// rendered this layout.
+    #[props(required)]
+    pub route: RouteInfo<T>,
+    #[props(required)]
+    children: ChildrenWithProps<Route<T, Outlet<T>>, Outlet<T>>,
+    /// Rendered when no child [Route](struct.Route.html) matches `route`, instead of silently
+    /// rendering nothing. Mirrors [Props::fallback](struct.Props.html#structfield.fallback) on
+    /// the top-level [Router].
+    #[props(required = false)]
+    pub fallback: Option<Html<Outlet<T>>>,
+}
+
+impl <T: for<'de> YewRouterState<'de>> Component for Outlet<T> {
+    type Message = ();
+    type Properties = OutletProps<T>;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        Outlet { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> bool {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props = props;
+        true
+    }
+}
+
+impl <T: for<'de> YewRouterState<'de>> Renderable<Outlet<T>> for Outlet<T> {
+    fn view(&self) -> VNode<Self> {
+        trace!("Routing one of {} nested routes for {:?}", self.props.children.iter().count(), &self.props.route);
+        self.props.children.iter()
+            .filter_map(|route_possibility| -> Option<Html<Self>> {
+                if !required_query_satisfied(&route_possibility.props.required_query, &self.props.route.query) {
+                    return None;
+                }
+
+                route_possibility.props.path
+                    .match_path(&self.props.route)
+                    .map(|(rest, mut hm)| {
+                        merge_query(&mut hm, &self.props.route.query);
+                        let nested_route = self.props.route.with_remaining(rest);
+                        (route_possibility.props.path.render_fn)(&hm, &nested_route)
+                    })
+                    .ok()
+                    .flatten_stable()
+            })
+            .next()
+            .or_else(|| self.props.fallback.clone())
+            .unwrap_or_else(|| {
+                warn!("Nested routing failed. No default case was provided.");
+                html! { <></>}
+            })
+    }
+}
+
+/// An imperative handle onto the router, usable without being a child of [Router](struct.Router.html).
+pub struct RouterHandle<T: for<'de> YewRouterState<'de>> {
+    route: Rc<RefCell<RouteInfo<T>>>,
+    agent: Box<dyn Bridge<RouteAgent<T>>>,
+}
+
+impl <T: for<'de> YewRouterState<'de>> RouterHandle<T> {
+    /// Creates a handle bridged to the [RouteAgent], forwarding route changes to `on_change`.
+    pub fn new(on_change: Callback<RouteInfo<T>>) -> Self {
+        let route = Rc::new(RefCell::new(RouteInfo::default()));
+        let route_for_callback = route.clone();
+        let callback = Callback::from(move |new_route: RouteInfo<T>| {
+            *route_for_callback.borrow_mut() = new_route.clone();
+            on_change.emit(new_route);
+        });
+        let mut agent = RouteAgent::bridge(callback);
+        agent.send(RouteRequest::GetCurrentRoute);
+        RouterHandle { route, agent }
+    }
+
+    /// Returns the most recently observed route.
+    pub fn current_route(&self) -> RouteInfo<T> {
+        self.route.borrow().clone()
+    }
+
+    /// Navigates to `route`, pushing a new history entry.
+    pub fn push(&mut self, route: RouteInfo<T>) {
+        self.agent.send(RouteRequest::ChangeRoute(route));
+    }
+
+    /// Navigates to `route`, replacing the current history entry.
+    pub fn replace(&mut self, route: RouteInfo<T>) {
+        self.agent.send(RouteRequest::ReplaceRoute(route));
+    }
+
+    /// Matches `path` against the current route and returns its merged path-capture/query
+    /// parameters, or `None` if it doesn't match.
+    pub fn matched_params<COMP: Component>(&self, path: &PathMatcher<COMP>) -> Option<HashMap<String, String>> {
+        let route = self.route.borrow();
+        let (_, mut hm) = path.match_path(&route).ok()?;
+        merge_query(&mut hm, &route.query);
+        Some(hm.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+}
+
+/// Merges `query`'s first value per key into `hm`, without overwriting an existing path capture.
+fn merge_query<'a>(hm: &mut HashMap<&'a str, &'a str>, query: &'a HashMap<String, Vec<String>>) {
+    for (key, values) in query.iter() {
+        if let Some(first) = values.first() {
+            hm.entry(key.as_str()).or_insert(first.as_str());
+        }
+    }
+}
+
+/// Converts a matched-params map into a sorted `(key, value)` vec, so it can be used as (part of)
+/// a [LoadKey] regardless of `HashMap` iteration order.
+fn sorted_params(hm: HashMap<&str, &str>) -> Vec<(String, String)> {
+    let mut params: Vec<(String, String)> = hm.into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    params.sort();
+    params
+}
+
+/// Checks that every [RequiredQuery] in `required` is present in `query` with a value that
+/// deserializes successfully.
+fn required_query_satisfied(required: &[RequiredQuery], query: &HashMap<String, Vec<String>>) -> bool {
+    required.iter().all(|req| {
+        query.get(&req.key)
+            .and_then(|values| values.first())
+            .map(|value| (req.deserialize)(value))
+            .unwrap_or(false)
+    })
+}
+
 trait Flatten<T> {
     /// Because flatten is a nightly feature. I'm making a new variant of the function here for stable use.
     /// The naming is changed to avoid this getting clobbered when object_flattening 60258 is stabilized.
@@ -0,0 +1,128 @@
+//! The fully-parsed current route: path, query, fragment, and history state.
+
+use std::collections::HashMap;
+
+/// Percent-decodes `s`, treating `+` as a space the way `application/x-www-form-urlencoded` does.
+fn decode_query_component(s: &str) -> String {
+    let with_spaces = s.replace('+', " ");
+    percent_encoding::percent_decode_str(&with_spaces)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// Parses a `key=value&key=value2` query string (without the leading `?`) into a map of decoded
+/// keys to all of their decoded values, in order, so repeated keys (`?tag=a&tag=b`) aren't lost.
+fn parse_query(query: &str) -> HashMap<String, Vec<String>> {
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, '=');
+        let key = decode_query_component(parts.next().unwrap_or(""));
+        let value = decode_query_component(parts.next().unwrap_or(""));
+        map.entry(key).or_insert_with(Vec::new).push(value);
+    }
+    map
+}
+
+/// The result of parsing the browser's current url (and its `history.state`) into its component
+/// parts, following the bundled `Route { path, query, hash, state }` shape rather than just a
+/// bare path.
+///
+/// For a route nested under an [Outlet](../component_router/struct.Outlet.html), `route` holds
+/// the unconsumed *remaining* tail of the parent's matched path rather than the full url path;
+/// `query`, `fragment` and `state` are carried through unchanged by
+/// [with_remaining](#method.with_remaining).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteInfo<T> {
+    /// The path portion of the url, e.g. `/users/42`.
+    pub route: String,
+    /// The query string, decoded into a key -> (ordered) values map.
+    pub query: HashMap<String, Vec<String>>,
+    /// The fragment after `#`, if any, without the leading `#`.
+    pub fragment: Option<String>,
+    /// The deserialized `history.state` associated with this route.
+    pub state: T,
+}
+
+impl<T> RouteInfo<T> {
+    /// Builds a `RouteInfo` from a full url (path, optional `?query`, optional `#fragment`) and
+    /// the already-deserialized history state.
+    pub fn new(url: &str, state: T) -> Self {
+        let (path_and_query, fragment) = match url.find('#') {
+            Some(index) => (&url[..index], Some(url[index + 1..].to_string())),
+            None => (url, None),
+        };
+        let (path, query) = match path_and_query.find('?') {
+            Some(index) => (
+                path_and_query[..index].to_string(),
+                parse_query(&path_and_query[index + 1..]),
+            ),
+            None => (path_and_query.to_string(), HashMap::new()),
+        };
+
+        RouteInfo {
+            route: path,
+            query,
+            fragment,
+            state,
+        }
+    }
+}
+
+impl<T: Clone> RouteInfo<T> {
+    /// Returns a copy of this `RouteInfo` with `route` replaced by `remaining`, keeping the same
+    /// `query`, `fragment` and `state`. Used to hand the unconsumed tail of a matched path down
+    /// to a nested [Outlet](../component_router/struct.Outlet.html).
+    pub fn with_remaining(&self, remaining: impl Into<String>) -> Self {
+        RouteInfo {
+            route: remaining.into(),
+            query: self.query.clone(),
+            fragment: self.fragment.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: Default> Default for RouteInfo<T> {
+    fn default() -> Self {
+        RouteInfo {
+            route: String::new(),
+            query: HashMap::new(),
+            fragment: None,
+            state: T::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_keys_collect_into_a_vec() {
+        let query = parse_query("tag=a&tag=b");
+        assert_eq!(query.get("tag"), Some(&vec!["a".to_string(), "b".to_string()]));
+    }
+
+    #[test]
+    fn plus_decodes_to_a_space() {
+        let query = parse_query("q=hello+world");
+        assert_eq!(query.get("q"), Some(&vec!["hello world".to_string()]));
+    }
+
+    #[test]
+    fn percent_encoded_bytes_are_decoded() {
+        let query = parse_query("q=a%26b");
+        assert_eq!(query.get("q"), Some(&vec!["a&b".to_string()]));
+    }
+
+    #[test]
+    fn valueless_key_decodes_to_an_empty_value() {
+        let query = parse_query("foo");
+        assert_eq!(query.get("foo"), Some(&vec!["".to_string()]));
+    }
+
+    #[test]
+    fn empty_query_string_is_an_empty_map() {
+        assert!(parse_query("").is_empty());
+    }
+}